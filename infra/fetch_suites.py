@@ -0,0 +1,63 @@
+#!/usr/bin/env python3
+
+import argparse
+import hashlib
+import io
+import json
+import tarfile
+import tempfile
+import urllib.request
+import zipfile
+from pathlib import Path
+
+def fetch_suite(suite, dest_dir):
+  # Archives are pinned by checksum so everyone benchmarks the same inputs
+  suite_dir = dest_dir / suite["name"]
+  if suite_dir.exists():
+    print(f"Already fetched: {suite['name']}")
+    return
+  print(f"Fetching {suite['name']} from {suite['url']}")
+  with urllib.request.urlopen(suite["url"]) as response:
+    archive = response.read()
+  digest = hashlib.sha256(archive).hexdigest()
+  if digest != suite["sha256"]:
+    raise SystemExit(
+      f"checksum mismatch for {suite['name']}: expected {suite['sha256']}, got {digest}"
+    )
+  # Unpack next to DEST_DIR/NAME and rename into place, so a failed
+  # extraction isn't mistaken for a fetched suite on the next run
+  dest_dir.mkdir(parents=True, exist_ok=True)
+  with tempfile.TemporaryDirectory(dir=dest_dir, prefix=f".{suite['name']}.") as tmp_dir:
+    unpacked_dir = Path(tmp_dir) / suite["name"]
+    if suite["url"].endswith(".zip"):
+      with zipfile.ZipFile(io.BytesIO(archive)) as zip_file:
+        zip_file.extractall(unpacked_dir)
+    else:
+      with tarfile.open(fileobj=io.BytesIO(archive)) as tar_file:
+        tar_file.extractall(unpacked_dir, filter="data")
+    unpacked_dir.rename(suite_dir)
+
+def main(args):
+  manifest = json.loads(args.manifest.read_text(encoding="utf-8"))
+  for suite in manifest["suites"]:
+    fetch_suite(suite, args.dest_dir)
+
+def parse_args():
+  parser = argparse.ArgumentParser(
+    description="Download and unpack benchmark suites into a local directory."
+  )
+  parser.add_argument(
+    "manifest",
+    type=Path,
+    help="JSON file of the form {\"suites\": [{\"name\": ..., \"url\": ..., \"sha256\": ...}]}, "
+      "where url is a .zip or a (possibly compressed) tar archive"
+  )
+  parser.add_argument(
+    "dest_dir",
+    type=Path,
+    help="directory to unpack each suite into, as DEST_DIR/NAME"
+  )
+  return parser.parse_args()
+
+if __name__ == "__main__":
+  main(parse_args())
@@ -1,10 +1,29 @@
 #!/usr/bin/env python3
 
+import argparse
+import csv
+import fnmatch
+import gzip
+import hashlib
 import json
+import os
+import platform
+import random
+import re
+import resource
+import shutil
+import signal
+import sqlite3
+import statistics
 import subprocess
 import sys
+import tempfile
+import threading
 import time
-from datetime import datetime, timezone
+import tomllib
+import xml.etree.ElementTree as ET
+import zlib
+from datetime import datetime, timedelta, timezone
 from pathlib import Path
 
 # Determine directories
@@ -12,46 +31,339 @@ SCRIPT_DIR = Path(__file__).resolve().parent
 POACH_ROOT = SCRIPT_DIR.parent
 NIGHTLY_DIR = POACH_ROOT / "nightly"
 POACH_BINARY = POACH_ROOT / "target" / "release" / "poach"
+OUTPUT_DIR = NIGHTLY_DIR / "output"
+# Per-benchmark logs, saved results, timeline CSVs and valgrind outputs
+REPORT_DIR = OUTPUT_DIR / "reports"
+SCRATCH_DIR = NIGHTLY_DIR / "scratch"
+# Decompressed copies of .gz and .zst benchmarks, which aren't published
+INPUT_DIR = NIGHTLY_DIR / "inputs"
+FAILURE_KINDS = [
+  "timeout", # killed after --timeout
+  "crash", # killed by a signal, e.g. a segfault or the OOM killer
+  "out_of_memory", # ran out of memory under --memory-limit-mb
+  "run_error", # exited with a nonzero status
+  "invalid_input", # the benchmark file couldn't be read or decompressed
+  "invalid_report" # exited successfully without a JSON report on stderr
+]
+# Bump whenever files under OUTPUT_DIR move or change shape, so downstream
+# tools can read manifest.json instead of hardcoding path conventions
+LAYOUT_VERSION = 2
 
-def main(benchmark_dir):
-  print(benchmark_dir)
+def main(args):
+  if args.merge_shards:
+    merge_shards(args.merge_shards)
+    return
+  print(args.benchmark_dir)
 
-  (benchmark_results, failing_benchmarks) = run_benchmarks(benchmark_dir)
+  manifest_entries = None
+  if args.benchmark_manifest is not None:
+    manifest_entries = load_benchmark_manifest(args.benchmark_manifest)
+  (benchmarks, skipped_benchmarks) = discover_benchmarks(
+    args.benchmark_dir,
+    args.extension or ["egg", "egg.gz", "egg.zst"],
+    args.filter,
+    args.skip_pattern,
+    args.exclude,
+    manifest_entries
+  )
+  expected_failures = set()
+  if args.skiplist is not None:
+    expected_failures = load_skiplist(args.skiplist)
+    if args.skiplist_mode == "skip":
+      for benchmark in list(benchmarks):
+        relative_path = benchmark.relative_to(args.benchmark_dir)
+        if str(relative_path) in expected_failures:
+          print(f"Skipping known failure: {relative_path}")
+          benchmarks.remove(benchmark)
+          skipped_benchmarks.append(relative_path)
+  aliases = {}
+  if args.dedup:
+    (benchmarks, aliases) = dedup_benchmarks(benchmarks, args.benchmark_dir)
+  if args.shard is not None:
+    # Discovery is sorted, so every machine agrees on the split
+    (index, count) = args.shard
+    benchmarks = benchmarks[index - 1::count]
+  if args.shuffle is not None:
+    # Without an explicit seed, pick one and record it so the order can be
+    # reproduced with --shuffle SEED
+    seed = random.randrange(2**32) if args.shuffle is True else args.shuffle
+    args.order = ("random", seed)
+  benchmarks = order_benchmarks(
+    benchmarks,
+    args.benchmark_dir,
+    args.order,
+    args.previous
+  )
+  if args.dry_run:
+    # Show what would run, to debug why a benchmark is missing
+    for benchmark in benchmarks:
+      relative_path = benchmark.relative_to(args.benchmark_dir)
+      print(
+        f"Would run: suite={relative_path.parent} benchmark={relative_path.name} "
+        f"result={saved_result_path(relative_path)}"
+      )
+    print(f"{len(benchmarks)} benchmark(s) to run, {len(skipped_benchmarks)} skipped")
+    return
+  # Collected up front so nothing can fail between the run and data.json
+  host = host_metadata()
+  (benchmark_results, failures, interrupted) = run_benchmarks(
+    args,
+    benchmarks,
+    expected_failures
+  )
+  completed = len(benchmark_results) + len(failures)
+
+  # Known failures are reported but not counted; a known failure that
+  # passes is reported so the skiplist can be pruned
+  known_failures = [f for f in failures if str(benchmark_path(f)) in expected_failures]
+  failures = [f for f in failures if f not in known_failures]
+  unexpected_passes = [
+    str(benchmark_path(r)) for r in benchmark_results
+    if str(benchmark_path(r)) in expected_failures
+  ]
+  for unexpected_pass in unexpected_passes:
+    print(f"Unexpected pass (remove from --skiplist): {unexpected_pass}")
+
+  if manifest_entries is not None:
+    apply_benchmark_manifest(
+      benchmark_results + failures + known_failures,
+      manifest_entries
+    )
+
+  if args.baseline is not None:
+    compare_to_baseline(benchmark_results, args.baseline, args.regression_threshold)
 
   data = {
     "generated_at": datetime.now(timezone.utc).isoformat(),
-    "failing_benchmarks": [str(b) for b in failing_benchmarks],
+    "host": host,
+    "interrupted": interrupted,
+    "shard": None if args.shard is None else "{}/{}".format(*args.shard),
+    "order": {
+      "strategy": args.order[0],
+      "seed": args.order[1],
+      "benchmarks": [str(b.relative_to(args.benchmark_dir)) for b in benchmarks]
+    },
+    # with --fail-fast, true if benchmarks were left unrun after a failure
+    "stopped_on_failure": not interrupted and completed < len(benchmarks),
+    "failing_benchmarks": [str(benchmark_path(f)) for f in failures],
+    # a subset of failing_benchmarks
+    "timed_out_benchmarks": [
+      str(benchmark_path(f)) for f in failures if f["status"] == "timeout"
+    ],
+    # failure_kind is one of FAILURE_KINDS, for grouping failures
+    "failure_details": [
+      {
+        "benchmark": str(benchmark_path(f)),
+        "failure_kind": f["failure_kind"],
+        "error": f.get("error")
+      }
+      for f in failures
+    ],
+    "skipped_benchmarks": [str(b) for b in skipped_benchmarks],
+    # with --skiplist, failures that were expected, and so not counted
+    "known_failures": [str(benchmark_path(f)) for f in known_failures],
+    "unexpected_passes": unexpected_passes,
+    # with --dedup, the byte-identical benchmarks not run, by the one that was
+    "aliases": aliases,
+    "regressed_benchmarks": [
+      str(benchmark_path(r))
+      for r in benchmark_results
+      if r.get("baseline", {}).get("regressed")
+    ],
     "passing_benchmarks": benchmark_results
   }
-  data_out_path = NIGHTLY_DIR / "output" / "data" / "data.json"
+  data_out_path = OUTPUT_DIR / "data" / "data.json"
   data_out_path.parent.mkdir(parents=True, exist_ok=True)
   data_out_path.write_text(json.dumps(data, indent=2), encoding="utf-8")
 
-def run_command(cmd):
+  manifest = {
+    "layout_version": LAYOUT_VERSION,
+    "generated_at": data["generated_at"],
+    "run_modes": ["serve"],
+    # paths are relative to the directory containing manifest.json
+    "paths": {
+      "data": str(data_out_path.relative_to(OUTPUT_DIR)),
+      "status": "status.json",
+      "events": "events.jsonl",
+      "reports": str(REPORT_DIR.relative_to(OUTPUT_DIR))
+    }
+  }
+
+  # The effective configuration (file plus command line) is kept for provenance
+  config_out_path = OUTPUT_DIR / "config.json"
+  config_out_path.write_text(
+    json.dumps(vars(args), indent=2, default=str),
+    encoding="utf-8"
+  )
+  manifest["paths"]["config"] = str(config_out_path.relative_to(OUTPUT_DIR))
+
+  if args.timeline_format == "csv":
+    write_timeline_csvs(benchmark_results)
+    manifest["paths"]["timeline_csv"] = "timeline.csv"
+
+  if args.history_db is not None:
+    record_history(args.history_db, data, benchmark_results + failures + known_failures)
+    regressions = history_regressions(
+      args.history_db,
+      data,
+      benchmark_results,
+      args.history_window,
+      args.regression_threshold
+    )
+    write_regressions(regressions, args.history_window)
+    manifest["paths"]["history_regressions"] = "regressions.json"
+
+  if args.junit is not None:
+    write_junit(args.junit, benchmark_results, failures, known_failures)
+
+  if args.repeat > 1:
+    stability_out_path = OUTPUT_DIR / "data" / "stability.json"
+    stability_out_path.write_text(
+      json.dumps(stability_report(benchmark_results, args.max_cv), indent=2),
+      encoding="utf-8"
+    )
+    manifest["paths"]["stability"] = str(stability_out_path.relative_to(OUTPUT_DIR))
+  (OUTPUT_DIR / "manifest.json").write_text(
+    json.dumps(manifest, indent=2),
+    encoding="utf-8"
+  )
+
+  if interrupted:
+    # Conventional exit status for termination by SIGINT
+    raise SystemExit(130)
+  if len(failures) > args.max_failures:
+    print(
+      f"{len(failures)} benchmark(s) failed, "
+      f"more than --max-failures {args.max_failures}"
+    )
+    raise SystemExit(1)
+
+def rusage_summary(rusage):
+  # Distinguishes CPU-bound slowdowns from paging or scheduler interference
+  return {
+    "user_micros": round(rusage.ru_utime * 1e6),
+    "sys_micros": round(rusage.ru_stime * 1e6),
+    "cpu_micros": round((rusage.ru_utime + rusage.ru_stime) * 1e6),
+    # includes the runner's own footprint, which the child has before exec
+    "max_rss_kb": rusage.ru_maxrss,
+    "minor_page_faults": rusage.ru_minflt,
+    "major_page_faults": rusage.ru_majflt,
+    "voluntary_context_switches": rusage.ru_nvcsw,
+    "involuntary_context_switches": rusage.ru_nivcsw
+  }
+
+def run_with_rusage(cmd, cwd, timeout, preexec_fn):
+  # subprocess.run can only report resource usage summed over all children
+  # (and so no per-benchmark peak RSS); reaping the child with wait4 gives
+  # its own. Output goes to files so a chatty child can't fill a pipe.
+  with tempfile.TemporaryFile() as stdout, tempfile.TemporaryFile() as stderr:
+    process = subprocess.Popen(
+      cmd,
+      cwd=cwd,
+      stdout=stdout,
+      stderr=stderr,
+      preexec_fn=preexec_fn
+    )
+    timed_out = threading.Event()
+    def kill():
+      timed_out.set()
+      process.kill()
+    timer = threading.Timer(timeout, kill) if timeout is not None else None
+    if timer is not None:
+      timer.start()
+    try:
+      (_, status, rusage) = os.wait4(process.pid, 0)
+    except KeyboardInterrupt:
+      # Like subprocess.run, don't leave the benchmark running
+      process.kill()
+      os.wait4(process.pid, 0)
+      raise
+    finally:
+      if timer is not None:
+        timer.cancel()
+    process.returncode = os.waitstatus_to_exitcode(status)
+    stdout.seek(0)
+    stderr.seek(0)
+    return (
+      process.returncode,
+      stdout.read().decode("utf-8", errors="replace"),
+      stderr.read().decode("utf-8", errors="replace"),
+      rusage,
+      timed_out.is_set()
+    )
+
+def write_log(log_path, stdout, stderr):
+  # Keeps the program's own output (print, check, ...) out of the console
+  log_path.parent.mkdir(parents=True, exist_ok=True)
+  log_path.write_text(
+    f"--- stdout ---\n{stdout}\n--- stderr ---\n{stderr}\n",
+    encoding="utf-8"
+  )
+
+def limit_memory(memory_limit_mb):
+  # Runs in the child before exec. Capping the address space makes
+  # allocations fail inside the benchmark instead of the whole machine
+  # running out of memory.
+  limit = memory_limit_mb * 1024 * 1024
+  resource.setrlimit(resource.RLIMIT_AS, (limit, limit))
+
+def run_command(cmd, timeout, log_path, memory_limit_mb, cwd):
   started = time.perf_counter_ns()
-  cmd_result = subprocess.run(
+  (returncode, stdout, stderr, rusage, timed_out) = run_with_rusage(
     cmd,
-    cwd=POACH_ROOT,
-    capture_output=True,
-    text=True # decode stderr/stdout as string instead of raw bytes
+    cwd,
+    timeout,
+    None if memory_limit_mb is None else lambda: limit_memory(memory_limit_mb)
   )
+  if timed_out:
+    write_log(log_path, stdout, stderr)
+    return {
+      "cmd": " ".join(cmd),
+      "status": "timeout",
+      "failure_kind": "timeout",
+      "wall_time_micros": (time.perf_counter_ns() - started) // 1000
+    }
   # Clock granularity is ~50-100 ns.
   # Report as micros to avoid reporting false precision.
   time_micros = (time.perf_counter_ns() - started) // 1000
-  if cmd_result.returncode != 0:
+  rusage = rusage_summary(rusage)
+  write_log(log_path, stdout, stderr)
+  # enough of stderr to explain a failure without bloating results
+  error = "\n".join(stderr.splitlines()[-20:])
+  if returncode != 0:
+    # a negative return code means the process was killed by a signal
+    failure_kind = "crash" if returncode < 0 else "run_error"
+    # Rust aborts with this message when an allocation fails
+    if memory_limit_mb is not None and "memory allocation of" in stderr:
+      failure_kind = "out_of_memory"
     return {
       "cmd": " ".join(cmd),
       "status": "error",
-      "wall_time_micros": time_micros
+      "failure_kind": failure_kind,
+      "error": error,
+      "wall_time_micros": time_micros,
+      "rusage": rusage
     }
 
-  report = json.loads(cmd_result.stderr)
+  try:
+    report = json.loads(stderr)
+  except json.JSONDecodeError:
+    return {
+      "cmd": " ".join(cmd),
+      "status": "error",
+      "failure_kind": "invalid_report",
+      "error": error,
+      "wall_time_micros": time_micros,
+      "rusage": rusage
+    }
 
   return {
     "cmd": " ".join(cmd),
     "status": "success",
     "report": summarize_report(report),
-    "wall_time_micros": time_micros
+    "timeline": report_timeline(report),
+    "wall_time_micros": time_micros,
+    "rusage": rusage
   }
   
 def summarize_report(report):
@@ -78,15 +390,744 @@ def summarize_report(report):
     "timing_steps": len(report["timings"])
   }
 
-def run_benchmarks(benchmark_dir):
-  report_dir = NIGHTLY_DIR / "reports"
-  report_dir.mkdir(parents=True, exist_ok=True)
+def report_timeline(report):
+  # The timing steps run one after another, so each starts where the
+  # previous one ended
+  timeline = []
+  start_micros = 0
+  for time_step in report["timings"]:
+    timeline.append({
+      "phase": "+".join(time_step["tags"]) or "other",
+      "start_micros": start_micros,
+      "duration_micros": time_step["total"]
+    })
+    start_micros += time_step["total"]
+  return timeline
 
-  # Find benchmarks
-  # benchmark_dir is the root of the benchmark directory 
-  benchmarks = list(Path(benchmark_dir).rglob("train/*.egg"))
+def write_timeline_csvs(results):
+  # One CSV per benchmark next to its log, plus all of them in one file
+  fields = ["benchmark", "phase", "start_micros", "duration_micros"]
+  all_rows = []
+  for result in results:
+    relative_path = benchmark_path(result)
+    # cached or resumed results may predate timelines
+    rows = [
+      {"benchmark": str(relative_path), **phase}
+      for phase in result.get("timeline", [])
+    ]
+    timeline_path = REPORT_DIR / relative_path.parent / f"{relative_path.name}.timeline.csv"
+    timeline_path.parent.mkdir(parents=True, exist_ok=True)
+    with open(timeline_path, "w", newline="", encoding="utf-8") as timeline_file:
+      writer = csv.DictWriter(timeline_file, fields)
+      writer.writeheader()
+      writer.writerows(rows)
+    all_rows.extend(rows)
+  with open(OUTPUT_DIR / "timeline.csv", "w", newline="", encoding="utf-8") as timeline_file:
+    writer = csv.DictWriter(timeline_file, fields)
+    writer.writeheader()
+    writer.writerows(all_rows)
+
+RULE_COMMANDS = {"rule", "rewrite", "birewrite"}
+RULESET_COMMANDS = {"ruleset", "unstable-combined-ruleset"}
+COMMAND_HEAD = re.compile(r"\s*([^\s()]+)")
+
+def read_benchmark(benchmark):
+  # Some corpora are shipped compressed; decompress them transparently
+  if benchmark.suffix == ".gz":
+    return gzip.decompress(benchmark.read_bytes())
+  if benchmark.suffix == ".zst":
+    # zstd is not in the standard library, so use the command-line tool
+    return subprocess.run(
+      ["zstd", "--decompress", "--stdout", str(benchmark)],
+      capture_output=True,
+      check=True
+    ).stdout
+  return benchmark.read_bytes()
+
+def input_metrics(source_bytes):
+  # Lightweight s-expression scan: count top-level commands, rules, and
+  # rulesets without depending on the egglog parser. Invalid UTF-8 is left
+  # for poach to reject.
+  source = source_bytes.decode("utf-8", errors="replace")
+  num_commands = 0
+  num_rules = 0
+  num_rulesets = 0
+  depth = 0
+  i = 0
+  while i < len(source):
+    c = source[i]
+    if c == ";":
+      i = source.find("\n", i)
+      if i == -1:
+        break
+    elif c == '"':
+      # skip string literals, honoring backslash escapes
+      i += 1
+      while i < len(source) and source[i] != '"':
+        i += 2 if source[i] == "\\" else 1
+    elif c == "(":
+      if depth == 0:
+        num_commands += 1
+        head = COMMAND_HEAD.match(source, i + 1)
+        if head and head.group(1) in RULE_COMMANDS:
+          num_rules += 1
+        elif head and head.group(1) in RULESET_COMMANDS:
+          num_rulesets += 1
+      depth += 1
+    elif c == ")":
+      depth -= 1
+    i += 1
+
+  return {
+    "source_bytes": len(source_bytes),
+    "num_commands": num_commands,
+    "num_rules": num_rules,
+    "num_rulesets": num_rulesets
+  }
+
+def is_excluded(relative_path, skip_regexes, excludes):
+  path = str(relative_path)
+  return any(regex.search(path) for regex in skip_regexes) or any(
+    pattern in path or fnmatch.fnmatch(path, pattern) for pattern in excludes
+  )
+
+def discover_benchmarks(benchmark_dir, extensions, filters, skip_patterns, excludes, manifest_entries):
+  # benchmark_dir is the root of the benchmark directory
   # For this treatment, we don't do anything at train time,
   # we just use the train benchmarks at serve time
+  skip_regexes = [re.compile(pattern) for pattern in skip_patterns]
+  benchmarks = []
+  skipped_benchmarks = []
+  candidates = set()
+  if manifest_entries is not None:
+    # A manifest lists the benchmarks explicitly instead of walking the tree
+    candidates.update(Path(benchmark_dir) / path for path in manifest_entries)
+  else:
+    for extension in extensions:
+      candidates.update(Path(benchmark_dir).rglob(f"train/*.{extension}"))
+  for benchmark in sorted(candidates):
+    relative_path = benchmark.relative_to(benchmark_dir)
+    # Filters select what to run, so filtered-out files are not reported
+    if filters and not any(fnmatch.fnmatch(str(relative_path), f) for f in filters):
+      continue
+    # Skipped files are reported so a renamed directory doesn't make
+    # benchmarks silently disappear from the nightly
+    if is_excluded(relative_path, skip_regexes, excludes):
+      print(f"Skipping: {relative_path}")
+      skipped_benchmarks.append(relative_path)
+    else:
+      benchmarks.append(benchmark)
+  return (benchmarks, skipped_benchmarks)
+
+def dedup_benchmarks(benchmarks, benchmark_dir):
+  # Runs the first of each set of byte-identical programs, comparing
+  # decompressed contents so a .egg.gz matches its .egg
+  representatives = {}
+  aliases = {}
+  deduped = []
+  for benchmark in benchmarks:
+    try:
+      digest = hashlib.sha256(read_benchmark(benchmark)).hexdigest()
+    except (OSError, EOFError, zlib.error, subprocess.CalledProcessError):
+      # Unreadable inputs are kept, to fail as invalid_input when they run
+      deduped.append(benchmark)
+      continue
+    if digest in representatives:
+      representative = str(representatives[digest].relative_to(benchmark_dir))
+      aliases.setdefault(representative, []).append(str(benchmark.relative_to(benchmark_dir)))
+    else:
+      representatives[digest] = benchmark
+      deduped.append(benchmark)
+  return (deduped, aliases)
+
+def load_skiplist(skiplist_path):
+  # One benchmark path (relative to benchmark_dir) per line; blank lines
+  # and '#' comments are ignored
+  skiplist = set()
+  for line in skiplist_path.read_text(encoding="utf-8").splitlines():
+    line = line.split("#", 1)[0].strip()
+    if line:
+      skiplist.add(str(Path(line)))
+  return skiplist
+
+def load_benchmark_manifest(manifest_path):
+  # {"benchmarks": [{"path": ..., "suite": ..., "tags": [...]}, ...]}, with
+  # paths relative to benchmark_dir; everything but the path is optional
+  manifest = json.loads(manifest_path.read_text(encoding="utf-8"))
+  for entry in manifest["benchmarks"]:
+    # poach serve doesn't report a tuple count to check these against
+    if "expected_tuples" in entry:
+      raise SystemExit(
+        f"{manifest_path}: expected_tuples (for {entry['path']}) is not supported, "
+        "since poach doesn't report tuple counts"
+      )
+  return {str(Path(entry["path"])): entry for entry in manifest["benchmarks"]}
+
+def apply_benchmark_manifest(results, manifest_entries):
+  # The manifest's suite is kept apart from suite_name, which (with
+  # benchmark_name) stays the on-disk path that baselines, history, and
+  # logs are keyed by
+  for result in results:
+    entry = manifest_entries[str(benchmark_path(result))]
+    result["manifest_suite"] = entry.get("suite")
+    result["tags"] = entry.get("tags", [])
+
+def host_metadata():
+  # Tells apart results from different machines and commits. Each probe is
+  # optional, so a missing tool records None instead of losing the run.
+  cpu_model = None
+  try:
+    for line in Path("/proc/cpuinfo").read_text(encoding="utf-8").splitlines():
+      if line.startswith("model name"):
+        cpu_model = line.split(":", 1)[1].strip()
+        break
+  except OSError:
+    pass
+  try:
+    # egglog is compiled into the poach binary, so this identifies both
+    poach_sha256 = binaries_digest([POACH_BINARY])
+  except OSError:
+    poach_sha256 = None
+  return {
+    "hostname": platform.node(),
+    "cpu_model": cpu_model,
+    "cpu_count": os.cpu_count(),
+    "total_memory_bytes": os.sysconf("SC_PAGE_SIZE") * os.sysconf("SC_PHYS_PAGES"),
+    "kernel": platform.release(),
+    "rustc_version": probe(["rustc", "--version"]),
+    # egglog lives in this repository, so this commit pins it too
+    "git_sha": probe(["git", "rev-parse", "HEAD"]),
+    "poach_sha256": poach_sha256
+  }
+
+def probe(cmd):
+  try:
+    return subprocess.run(
+      cmd,
+      cwd=POACH_ROOT,
+      capture_output=True,
+      text=True,
+      check=True,
+      # rustup may try to install the pinned toolchain
+      timeout=60
+    ).stdout.strip()
+  except (OSError, subprocess.CalledProcessError, subprocess.TimeoutExpired):
+    return None
+
+def binaries_digest(binaries):
+  # poach --version is the crate version, which stays the same across
+  # commits (and egglog changes), so identify the binaries by their contents
+  key = hashlib.sha256()
+  for binary in binaries:
+    key.update(binary.read_bytes())
+  return key.hexdigest()
+
+def cache_path(cache_dir, binaries, benchmark, mode):
+  # Key on everything that can change the result: the input contents, the
+  # binaries' contents, and the run mode
+  key = hashlib.sha256()
+  key.update(benchmark.read_bytes())
+  key.update(binaries.encode("utf-8"))
+  key.update(json.dumps(mode).encode("utf-8"))
+  return cache_dir / f"{key.hexdigest()}.json"
+
+def callgrind_instructions(out_file):
+  # The first event in callgrind's summary line is Ir (instructions executed)
+  for line in out_file.read_text(encoding="utf-8").splitlines():
+    if line.startswith(("summary:", "totals:")):
+      return int(line.split()[1])
+  return None
+
+def massif_peak_heap(out_file):
+  # Peak over all snapshots of the useful heap plus allocator overhead
+  peak = 0
+  snapshot = 0
+  for line in out_file.read_text(encoding="utf-8").splitlines():
+    if line.startswith("mem_heap_B="):
+      snapshot = int(line.split("=")[1])
+    elif line.startswith("mem_heap_extra_B="):
+      peak = max(peak, snapshot + int(line.split("=")[1]))
+  return peak
+
+def valgrind_wrapper(tool, out_file):
+  return [
+    "valgrind",
+    f"--tool={tool}",
+    f"--{tool}-out-file={out_file}",
+    # valgrind's banner and summary would otherwise go to stderr, which
+    # must hold only poach's JSON report
+    f"--log-file={out_file.with_suffix('.log')}"
+  ]
+
+def record_valgrind(tool, out_file, result):
+  if result["status"] != "success":
+    return
+  if tool == "callgrind":
+    result["instructions"] = callgrind_instructions(out_file)
+  else:
+    result["peak_heap_bytes"] = massif_peak_heap(out_file)
+
+def reset_dir(path):
+  shutil.rmtree(path, ignore_errors=True)
+  path.mkdir(parents=True)
+
+def run_repeated(cmd, warmup, repeat, timeout, log_path, memory_limit_mb, cwd):
+  # Reports the last run, with the median wall time of the measured runs
+  # (so baseline, history and JUnit see the typical run rather than
+  # whichever came last) and every sample; warmup runs only need to
+  # succeed. The log is of the last run.
+  samples = []
+  for i in range(warmup + repeat):
+    result = run_command(cmd, timeout, log_path, memory_limit_mb, cwd)
+    if result["status"] != "success":
+      return result
+    if i >= warmup:
+      samples.append(result["wall_time_micros"])
+  result["wall_time_samples_micros"] = samples
+  result["wall_time_stats_micros"] = {
+    "min": min(samples),
+    "median": statistics.median(samples),
+    "mean": statistics.mean(samples),
+    "stddev": statistics.stdev(samples) if len(samples) > 1 else 0.0
+  }
+  result["wall_time_micros"] = round(statistics.median(samples))
+  return result
+
+def stability_report(results, max_cv):
+  # Benchmarks whose coefficient of variation exceeds max_cv are too noisy
+  # to gate on without more repetitions
+  report = []
+  for result in results:
+    samples = result["wall_time_samples_micros"]
+    mean = statistics.mean(samples)
+    cv = statistics.stdev(samples) / mean if mean > 0 else 0.0
+    report.append({
+      "suite_name": result["suite_name"],
+      "benchmark_name": result["benchmark_name"],
+      "repetitions": len(samples),
+      "coefficient_of_variation": cv,
+      "noisy": cv > max_cv
+    })
+  return report
+
+def write_junit(path, results, failures, known_failures):
+  # One test case per benchmark, so CI can render the run as a test report;
+  # known failures are reported as skipped
+  suite = ET.Element(
+    "testsuite",
+    name="poach-nightly",
+    tests=str(len(results) + len(failures) + len(known_failures)),
+    failures=str(len(failures)),
+    skipped=str(len(known_failures))
+  )
+  for result in results + failures + known_failures:
+    case = ET.SubElement(
+      suite,
+      "testcase",
+      classname=result["suite_name"],
+      name=result["benchmark_name"],
+      time=f"{result['wall_time_micros'] / 1e6:.6f}"
+    )
+    if result in known_failures:
+      skipped = ET.SubElement(case, "skipped", message=f"known failure: {result['status']}")
+      skipped.text = result.get("error", result["cmd"])
+    elif result["status"] != "success":
+      failure = ET.SubElement(case, "failure", message=result["status"])
+      failure.text = result.get("error", result["cmd"])
+  path.parent.mkdir(parents=True, exist_ok=True)
+  ET.ElementTree(suite).write(path, encoding="utf-8", xml_declaration=True)
+
+def record_history(db_path, data, results):
+  # One row per benchmark per night, so trends can be queried without
+  # re-reading every night's data.json; rerunning a night replaces its rows
+  with sqlite3.connect(db_path) as db:
+    db.execute(
+      """CREATE TABLE IF NOT EXISTS results (
+        date TEXT NOT NULL,
+        git_sha TEXT NOT NULL,
+        benchmark TEXT NOT NULL,
+        status TEXT NOT NULL,
+        failure_kind TEXT,
+        wall_time_micros INTEGER,
+        rule_micros INTEGER,
+        extraction_micros INTEGER,
+        other_micros INTEGER,
+        source_bytes INTEGER,
+        PRIMARY KEY (date, git_sha, benchmark)
+      )"""
+    )
+    date = data["generated_at"][:len("YYYY-MM-DD")]
+    # git_sha is None outside a git checkout, but is part of the key
+    for result in results:
+      report = result.get("report", {})
+      db.execute(
+        "INSERT OR REPLACE INTO results VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        (
+          date,
+          data["host"]["git_sha"] or "unknown",
+          str(benchmark_path(result)),
+          result["status"],
+          result.get("failure_kind"),
+          result["wall_time_micros"],
+          report.get("rule_micros"),
+          report.get("extraction_micros"),
+          report.get("other_micros"),
+          (result["input"] or {}).get("source_bytes")
+        )
+      )
+  db.close()
+
+HISTORY_METRICS = ["wall_time_micros", "rule_micros", "extraction_micros", "other_micros"]
+
+def history_regressions(db_path, data, results, window, threshold_percent):
+  # Compares this run against the median of each benchmark's last `window`
+  # successful runs, which is less noisy than a single baseline run
+  regressions = []
+  with sqlite3.connect(db_path) as db:
+    for result in results:
+      rows = db.execute(
+        f"SELECT {', '.join(HISTORY_METRICS)} FROM results "
+        "WHERE benchmark = ? AND status = 'success' AND (date, git_sha) != (?, ?) "
+        "ORDER BY date DESC LIMIT ?",
+        (
+          str(benchmark_path(result)),
+          data["generated_at"][:len("YYYY-MM-DD")],
+          data["host"]["git_sha"] or "unknown",
+          window
+        )
+      ).fetchall()
+      if not rows:
+        continue
+      latest = {"wall_time_micros": result["wall_time_micros"], **result["report"]}
+      for (i, metric) in enumerate(HISTORY_METRICS):
+        median = statistics.median(row[i] for row in rows)
+        if median > 0 and latest[metric] > median * (1 + threshold_percent / 100):
+          regressions.append({
+            "benchmark": str(benchmark_path(result)),
+            "metric": metric,
+            "median": median,
+            "latest": latest[metric],
+            "delta_percent": 100 * (latest[metric] - median) / median,
+            "runs": len(rows)
+          })
+  db.close()
+  return regressions
+
+def write_regressions(regressions, window):
+  (OUTPUT_DIR / "regressions.json").write_text(
+    json.dumps(regressions, indent=2),
+    encoding="utf-8"
+  )
+  lines = [
+    f"# Regressions against the median of the last {window} runs",
+    "",
+    "| Benchmark | Metric | Median | Latest | Change |",
+    "| --- | --- | --- | --- | --- |"
+  ]
+  for regression in regressions:
+    lines.append(
+      f"| {regression['benchmark']} | {regression['metric']} | {regression['median']} "
+      f"| {regression['latest']} | +{regression['delta_percent']:.1f}% |"
+    )
+  (OUTPUT_DIR / "regressions.md").write_text("\n".join(lines) + "\n", encoding="utf-8")
+  for regression in regressions:
+    print(
+      f"Regression: {regression['benchmark']} {regression['metric']} "
+      f"+{regression['delta_percent']:.1f}% over the median of {regression['runs']} run(s)"
+    )
+
+def load_manifest_paths(output_dir):
+  # Older output dirs predate manifest.json
+  manifest_path = output_dir / "manifest.json"
+  if not manifest_path.exists():
+    return {"data": "data/data.json"}
+  return json.loads(manifest_path.read_text(encoding="utf-8"))["paths"]
+
+def load_data(output_dir):
+  data_path = output_dir / load_manifest_paths(output_dir)["data"]
+  return json.loads(data_path.read_text(encoding="utf-8"))
+
+def compare_to_baseline(results, baseline_dir, threshold_percent):
+  baseline = {
+    str(benchmark_path(r)): r for r in load_data(baseline_dir)["passing_benchmarks"]
+  }
+  for result in results:
+    old = baseline.get(str(benchmark_path(result)))
+    if old is None:
+      continue
+    old_metrics = {"wall_time_micros": old["wall_time_micros"], **old["report"]}
+    new_metrics = {"wall_time_micros": result["wall_time_micros"], **result["report"]}
+    deltas = {}
+    for (metric, old_value) in old_metrics.items():
+      delta = {"baseline": old_value, "delta": new_metrics[metric] - old_value}
+      if old_value > 0:
+        delta["delta_percent"] = 100 * delta["delta"] / old_value
+      deltas[metric] = delta
+    result["baseline"] = {
+      "metrics": deltas,
+      # only wall time is gated on; the breakdown explains where it moved
+      "regressed": deltas["wall_time_micros"].get("delta_percent", 0) > threshold_percent
+    }
+
+def benchmark_path(result):
+  # Path of the benchmark relative to benchmark_dir
+  return Path(result["suite_name"]) / result["benchmark_name"]
+
+def estimate_eta(elapsed, completed, total):
+  # Assumes the remaining benchmarks take the mean time of completed ones
+  if completed == 0:
+    return None
+  return round(elapsed / completed * (total - completed))
+
+def show_progress(completed, total, benchmark, started, last_duration):
+  elapsed = time.monotonic() - started
+  line = f"[{completed + 1}/{total}] {benchmark} | elapsed {timedelta(seconds=round(elapsed))}"
+  if last_duration is not None:
+    line += f" | last {last_duration:.2f}s"
+  eta = estimate_eta(elapsed, completed, total)
+  if eta is not None:
+    line += f" | ETA {timedelta(seconds=eta)}"
+  # Redraw in place; clear_progress removes it before other output
+  print(f"\r\033[K{line}", end="", flush=True)
+
+def clear_progress():
+  print("\r\033[K", end="", flush=True)
+
+def emit_event(event, **fields):
+  # One JSON object per line, appended and flushed immediately so that
+  # external monitors can tail the run
+  OUTPUT_DIR.mkdir(parents=True, exist_ok=True)
+  with open(OUTPUT_DIR / "events.jsonl", "a", encoding="utf-8") as events:
+    line = {"event": event, "time": datetime.now(timezone.utc).isoformat(), **fields}
+    events.write(json.dumps(line) + "\n")
+
+def write_status(state, benchmark, completed, total, failures, started):
+  # Lets dashboards and ops scripts monitor a run without tailing logs
+  elapsed = time.monotonic() - started
+  eta_seconds = estimate_eta(elapsed, completed, total)
+  status = {
+    "state": state,
+    "current_benchmark": None if benchmark is None else str(benchmark),
+    "completed": completed,
+    "total": total,
+    "failures": [str(benchmark_path(f)) for f in failures],
+    "elapsed_seconds": round(elapsed),
+    "eta_seconds": eta_seconds,
+    "updated_at": datetime.now(timezone.utc).isoformat()
+  }
+  OUTPUT_DIR.mkdir(parents=True, exist_ok=True)
+  write_atomic(OUTPUT_DIR / "status.json", json.dumps(status, indent=2))
+
+def write_atomic(path, text):
+  # Write then rename so readers never see a partially written file, even
+  # if the run is interrupted mid-write
+  tmp_path = path.with_name(f"{path.name}.tmp")
+  tmp_path.write_text(text, encoding="utf-8")
+  tmp_path.replace(path)
+
+SERVE_ARGS = ["serve", "--debug", "EMPTY.MODEL", "single"]
+
+def selected_valgrind_tool(args):
+  # Instruction counts (callgrind) are deterministic, unlike sampled
+  # timings, so they can resolve regressions smaller than the run-to-run
+  # noise; massif tracks peak heap for memory regressions
+  return next(
+    (tool for tool in ["callgrind", "massif"] if getattr(args, tool)),
+    None
+  )
+
+def run_mode(args):
+  # The flags that change what a result measures; cached and resumed
+  # results are only reused when these match
+  mode = list(SERVE_ARGS)
+  valgrind_tool = selected_valgrind_tool(args)
+  if valgrind_tool is not None:
+    mode.append(valgrind_tool)
+  if args.warmup > 0:
+    mode.append(f"warmup={args.warmup}")
+  if args.repeat > 1:
+    mode.append(f"repeat={args.repeat}")
+  if args.compare_bin is not None:
+    mode.append(f"compare-bin={args.compare_bin}")
+  return mode
+
+def run_benchmark(args, benchmark, binaries, mode):
+  relative_path = benchmark.relative_to(args.benchmark_dir)
+  try:
+    source_bytes = read_benchmark(benchmark)
+  except (OSError, EOFError, zlib.error, subprocess.CalledProcessError) as err:
+    # A corrupt input fails its own benchmark instead of the whole run
+    return {
+      "cmd": f"read {benchmark}",
+      "status": "error",
+      "failure_kind": "invalid_input",
+      "error": str(err),
+      "wall_time_micros": 0,
+      "benchmark_name": relative_path.name,
+      "suite_name": str(relative_path.parent),
+      "input": None,
+      "mode": mode
+    }
+  program = benchmark
+  if benchmark.suffix in [".gz", ".zst"]:
+    # poach reads plain .egg files, so run a decompressed copy
+    program = INPUT_DIR / relative_path.with_suffix("")
+    program.parent.mkdir(parents=True, exist_ok=True)
+    program.write_bytes(source_bytes)
+  serve_command = [str(POACH_BINARY)] + SERVE_ARGS + [
+    # absolute, since the benchmark runs in its scratch directory
+    str(program.resolve())
+  ]
+  command = serve_command
+  valgrind_tool = selected_valgrind_tool(args)
+  if valgrind_tool is not None:
+    valgrind_out = REPORT_DIR / relative_path.with_suffix(f".{valgrind_tool}.out")
+    valgrind_out.parent.mkdir(parents=True, exist_ok=True)
+    command = valgrind_wrapper(valgrind_tool, valgrind_out) + serve_command
+
+  cached = None
+  if args.cache is not None:
+    cached = cache_path(args.cache, binaries, benchmark, mode)
+  if cached is not None and cached.exists():
+    result = json.loads(cached.read_text(encoding="utf-8"))
+    result["cached"] = True
+  else:
+    log_path = REPORT_DIR / relative_path.parent / f"{relative_path.name}.log"
+    # Each benchmark runs in its own directory, so programs that write
+    # relative-path outputs don't collide with each other or the repo
+    scratch_dir = SCRATCH_DIR / relative_path
+    reset_dir(scratch_dir)
+    try:
+      result = run_repeated(
+        command,
+        args.warmup,
+        args.repeat,
+        args.timeout,
+        log_path,
+        args.memory_limit_mb,
+        scratch_dir
+      )
+      if valgrind_tool is not None:
+        record_valgrind(valgrind_tool, valgrind_out, result)
+      if args.compare_bin is not None:
+        compare_command = [str(args.compare_bin)] + serve_command[1:]
+        if valgrind_tool is not None:
+          # its own out-file, so the first run's counts aren't overwritten
+          compare_out = valgrind_out.with_suffix(".compare.out")
+          compare_command = valgrind_wrapper(valgrind_tool, compare_out) + compare_command
+        compare_log_path = log_path.with_suffix(".compare.log")
+        # start from the same empty directory as the first run
+        reset_dir(scratch_dir)
+        comparison = run_repeated(
+          compare_command,
+          args.warmup,
+          args.repeat,
+          args.timeout,
+          compare_log_path,
+          args.memory_limit_mb,
+          scratch_dir
+        )
+        comparison["log"] = str(compare_log_path.relative_to(OUTPUT_DIR))
+        if valgrind_tool is not None:
+          record_valgrind(valgrind_tool, compare_out, comparison)
+        result["comparison"] = compare_runs(result, comparison)
+    except KeyboardInterrupt:
+      # Don't leave an interrupted benchmark's temporary files behind
+      shutil.rmtree(scratch_dir, ignore_errors=True)
+      if program != benchmark:
+        program.unlink(missing_ok=True)
+      raise
+    if args.keep_scratch:
+      # scratch directories aren't published, so this is relative to the
+      # nightly directory rather than the output
+      result["scratch_dir"] = str(scratch_dir.relative_to(NIGHTLY_DIR))
+    else:
+      shutil.rmtree(scratch_dir)
+    result["log"] = str(log_path.relative_to(OUTPUT_DIR))
+    # Only successful results are cached so failures are always retried
+    if cached is not None and result["status"] == "success":
+      write_atomic(cached, json.dumps(result))
+  result["benchmark_name"] = relative_path.name
+  result["suite_name"] = str(relative_path.parent)
+  result["input"] = input_metrics(source_bytes)
+  result["mode"] = mode
+  result["binaries"] = binaries
+  return result
+
+def compare_runs(result, comparison):
+  # Lines up the two binaries' timelines phase by phase. The timelines
+  # only match up when both runs succeed.
+  comparison["phases"] = [
+    {
+      "phase": phase["phase"],
+      "duration_micros": phase["duration_micros"],
+      "compare_duration_micros": compare_phase["duration_micros"]
+    }
+    for (phase, compare_phase) in zip(
+      result.get("timeline", []),
+      comparison.get("timeline", [])
+    )
+  ]
+  if (result["status"] == "success" and comparison["status"] == "success"
+      and result["wall_time_micros"] > 0):
+    comparison["wall_time_delta_percent"] = 100 * (
+      comparison["wall_time_micros"] - result["wall_time_micros"]
+    ) / result["wall_time_micros"]
+  return comparison
+
+def order_benchmarks(benchmarks, benchmark_dir, order, previous):
+  # With a wall-clock budget or an early abort, running the most
+  # informative benchmarks first matters more than a stable order
+  (strategy, seed) = order
+  if strategy == "name":
+    return benchmarks
+  if strategy == "random":
+    benchmarks = list(benchmarks)
+    random.Random(seed).shuffle(benchmarks)
+    return benchmarks
+
+  if previous is None:
+    raise SystemExit(f"--order {strategy} requires --previous")
+  previous_data = json.loads(previous.read_text(encoding="utf-8"))
+  wall_times = {
+    str(Path(b["suite_name"]) / b["benchmark_name"]): b["wall_time_micros"]
+    for b in previous_data["passing_benchmarks"]
+  }
+  previous_failures = set(previous_data["failing_benchmarks"])
+
+  def key(benchmark):
+    relative_path = str(benchmark.relative_to(benchmark_dir))
+    if strategy == "failures-first":
+      return relative_path not in previous_failures
+    # slowest-first: benchmarks without a previous time (new or previously
+    # failing) go first, since nothing is known about them
+    return -wall_times.get(relative_path, float("inf"))
+
+  # sorted is stable, so ties keep their alphabetical order
+  return sorted(benchmarks, key=key)
+
+def parse_order(value):
+  match = re.fullmatch(r"random\((\d+)\)", value)
+  if match:
+    return ("random", int(match.group(1)))
+  if value in ["name", "slowest-first", "failures-first"]:
+    return (value, None)
+  raise argparse.ArgumentTypeError(
+    f"invalid order {value!r}: expected name, slowest-first, failures-first, or random(SEED)"
+  )
+
+def saved_result_path(relative_path):
+  return REPORT_DIR / relative_path.parent / f"{relative_path.name}.result.json"
+
+def run_benchmarks(args, benchmarks, expected_failures):
+  REPORT_DIR.mkdir(parents=True, exist_ok=True)
+  mode = run_mode(args)
+  # Both --cache and --resume reuse results only from the same binaries
+  binaries = binaries_digest(
+    [POACH_BINARY] + ([] if args.compare_bin is None else [args.compare_bin])
+  )
+  if args.cache is not None:
+    args.cache.mkdir(parents=True, exist_ok=True)
 
   # TODO: invoke the poach commands appropriate for this branch (e.g.
   # `poach train ...` and/or `poach serve ...`) for each benchmark file
@@ -95,33 +1136,478 @@ def run_benchmarks(benchmark_dir):
   # "wall_time_micros" (plus any branch-specific fields like "phase").
 
   results = []
-  failing_benchmarks = []
-  for benchmark in benchmarks:
-    relative_path = benchmark.relative_to(benchmark_dir)
-    suite_name = str(relative_path.parent)
-    benchmark_name = relative_path.name
-    command = [
-      str(POACH_BINARY),
-      "serve",
-      "--debug",
-      "EMPTY.MODEL",
-      "single",
-      str(benchmark)
+  failures = []
+  interrupted = False
+  started = time.monotonic()
+  # A redrawn progress line only makes sense on a terminal
+  progress = not args.no_progress and sys.stdout.isatty()
+  last_duration = None
+  emit_event("run_started", total=len(benchmarks))
+  try:
+    for (i, benchmark) in enumerate(benchmarks):
+      relative_path = benchmark.relative_to(args.benchmark_dir)
+      if progress:
+        show_progress(i, len(benchmarks), relative_path, started, last_duration)
+      benchmark_started = time.monotonic()
+      emit_event("benchmark_started", benchmark=str(relative_path))
+      write_status(
+        "running",
+        relative_path,
+        i,
+        len(benchmarks),
+        failures,
+        started
+      )
+      # Every result is saved as soon as it completes, so an interrupted
+      # run can be resumed with only the remaining benchmarks
+      saved_result = saved_result_path(relative_path)
+      saved = None
+      if args.resume and saved_result.exists():
+        saved = json.loads(saved_result.read_text(encoding="utf-8"))
+      # A result saved with other flags (e.g. a different --repeat) or
+      # binaries measured something else, and failures are always retried,
+      # so only matching successes are reused
+      if (
+        saved is not None
+        and saved["status"] == "success"
+        and saved.get("mode") == mode
+        and saved.get("binaries") == binaries
+      ):
+        result = saved
+        result["resumed"] = True
+      else:
+        result = run_benchmark(args, benchmark, binaries, mode)
+        saved_result.parent.mkdir(parents=True, exist_ok=True)
+        write_atomic(saved_result, json.dumps(result))
+      last_duration = time.monotonic() - benchmark_started
+      emit_event(
+        "benchmark_finished",
+        benchmark=str(relative_path),
+        status=result["status"],
+        failure_kind=result.get("failure_kind"),
+        wall_time_micros=result["wall_time_micros"],
+        report=result.get("report")
+      )
+      if progress:
+        clear_progress()
+      if result["status"] == "success":
+        print(f"Success: {result['benchmark_name']}")
+        results.append(result)
+      else:
+        if result["status"] == "timeout":
+          print(f"Timeout: {result['benchmark_name']}")
+        failures.append(result)
+        # known failures are expected, so they don't stop the run
+        if args.fail_fast and str(relative_path) not in expected_failures:
+          print(f"Stopping after first failure: {relative_path}")
+          break
+  except KeyboardInterrupt:
+    # The interrupted benchmark is dropped, but everything that completed
+    # before it is still written out
+    if progress:
+      clear_progress()
+    print("Interrupted, writing partial results")
+    interrupted = True
+
+  completed = len(results) + len(failures)
+  if interrupted:
+    state = "interrupted"
+  elif completed < len(benchmarks):
+    state = "stopped_on_failure"
+  else:
+    state = "finished"
+  emit_event("run_finished", state=state, completed=completed, failures=len(failures))
+  write_status(
+    state,
+    None,
+    completed,
+    len(benchmarks),
+    failures,
+    started
+  )
+  return (results, failures, interrupted)
+
+def parse_args():
+  # --config is parsed first so the file's values can become defaults
+  # that flags given on the command line override
+  config_parser = argparse.ArgumentParser(add_help=False)
+  config_parser.add_argument(
+    "--config",
+    type=Path,
+    metavar="TOML",
+    help="read defaults for any of these options from a TOML file, e.g. "
+      "'timeout = 60' or 'filter = [\"suite/*\"]'; list options given on "
+      "the command line add to the file's lists"
+  )
+  (config_args, _) = config_parser.parse_known_args()
+
+  parser = argparse.ArgumentParser(
+    description="Run the POACH nightly benchmarks.",
+    parents=[config_parser]
+  )
+  parser.add_argument(
+    "benchmark_dir",
+    nargs="?",
+    help="root of the benchmark directory (required unless set in --config)"
+  )
+  parser.add_argument(
+    "--skiplist",
+    type=Path,
+    metavar="TXT",
+    help="benchmarks that are expected to fail, one path (relative to benchmark_dir) "
+      "per line; their failures are not counted, and passes are reported"
+  )
+  parser.add_argument(
+    "--skiplist-mode",
+    choices=["run", "skip"],
+    default="run",
+    help="run (default) skiplisted benchmarks without counting their failures, "
+      "or skip them entirely"
+  )
+  parser.add_argument(
+    "--dedup",
+    action="store_true",
+    help="run only one of each set of byte-identical benchmarks, recording the "
+      "others as aliases in data.json"
+  )
+  parser.add_argument(
+    "--shard",
+    type=parse_shard,
+    metavar="K/N",
+    help="run only the K-th of N deterministic slices of the benchmarks, "
+      "e.g. to split a suite across N CI machines"
+  )
+  parser.add_argument(
+    "--merge-shards",
+    nargs="+",
+    type=Path,
+    metavar="OUTPUT_DIR",
+    help="instead of running benchmarks, merge the output dirs of --shard runs "
+      "into nightly/output"
+  )
+  parser.add_argument(
+    "--benchmark-manifest",
+    type=Path,
+    metavar="JSON",
+    help="run the benchmarks listed in JSON (paths relative to benchmark_dir, "
+      "with optional suite and tags) instead of walking benchmark_dir"
+  )
+  parser.add_argument(
+    "--extension",
+    action="append",
+    metavar="EXT",
+    help="file extension of benchmark programs (default: egg, egg.gz, and egg.zst); repeatable"
+  )
+  parser.add_argument(
+    "--cache",
+    type=Path,
+    metavar="DIR",
+    help="reuse results for benchmarks whose input, poach binary, and command are unchanged"
+  )
+  valgrind = parser.add_mutually_exclusive_group()
+  valgrind.add_argument(
+    "--callgrind",
+    action="store_true",
+    help="run each benchmark under valgrind's callgrind and record its instruction count"
+  )
+  valgrind.add_argument(
+    "--massif",
+    action="store_true",
+    help="run each benchmark under valgrind's massif and record its peak heap size"
+  )
+  parser.add_argument(
+    "--timeout",
+    type=float,
+    metavar="SECS",
+    help="kill a benchmark after SECS seconds and record it as a timeout failure"
+  )
+  parser.add_argument(
+    "--memory-limit-mb",
+    type=int,
+    metavar="MB",
+    help="limit each benchmark's address space to MB megabytes and record "
+      "exhausting it as an out_of_memory failure"
+  )
+  parser.add_argument(
+    "--no-progress",
+    action="store_true",
+    help="don't show a progress line with an ETA (it is only shown on a terminal)"
+  )
+  parser.add_argument(
+    "--dry-run",
+    action="store_true",
+    help="list the benchmarks that would run, with their output paths, without running them"
+  )
+  parser.add_argument(
+    "--compare-bin",
+    type=Path,
+    metavar="POACH",
+    help="also run each benchmark with another poach binary (e.g. built against "
+      "another egglog revision) and record the two timelines side by side"
+  )
+  parser.add_argument(
+    "--keep-scratch",
+    action="store_true",
+    help="keep each benchmark's scratch working directory under nightly/scratch "
+      "instead of removing it after the benchmark finishes"
+  )
+  parser.add_argument(
+    "--timeline-format",
+    choices=["json", "csv"],
+    default="json",
+    help="json (default) keeps each benchmark's timeline in data.json; csv also "
+      "writes it as CSV next to the benchmark's log and to nightly/output/timeline.csv"
+  )
+  parser.add_argument(
+    "--history-db",
+    type=Path,
+    metavar="SQLITE",
+    help="also append each benchmark's timings, sizes, and status to the SQLite "
+      "database SQLITE, keyed by (date, commit, benchmark), and report "
+      "regressions against its history"
+  )
+  parser.add_argument(
+    "--history-window",
+    type=int,
+    default=7,
+    metavar="RUNS",
+    help="with --history-db, compare against the median of each benchmark's "
+      "last RUNS successful runs (default: 7)"
+  )
+  parser.add_argument(
+    "--junit",
+    type=Path,
+    metavar="PATH",
+    help="also write the results as a JUnit XML test suite to PATH"
+  )
+  parser.add_argument(
+    "--fail-fast",
+    action="store_true",
+    help="stop at the first failing benchmark that isn't in --skiplist, still writing "
+      "results for those that ran"
+  )
+  parser.add_argument(
+    "--max-failures",
+    type=int,
+    default=0,
+    metavar="N",
+    help="exit with a nonzero status if more than N benchmarks fail (default: 0)"
+  )
+  parser.add_argument(
+    "--resume",
+    action="store_true",
+    help="reuse per-benchmark results saved by a previous, interrupted run"
+  )
+  parser.add_argument(
+    "--warmup",
+    type=int,
+    default=0,
+    metavar="K",
+    help="run each benchmark K extra times before measuring (default: 0)"
+  )
+  parser.add_argument(
+    "--repeat",
+    type=int,
+    default=1,
+    metavar="N",
+    help="run each benchmark N times and write a stability report (default: 1)"
+  )
+  parser.add_argument(
+    "--max-cv",
+    type=float,
+    default=0.05,
+    metavar="CV",
+    help="flag benchmarks whose wall-time coefficient of variation exceeds CV as noisy (default: 0.05)"
+  )
+  parser.add_argument(
+    "--baseline",
+    type=Path,
+    metavar="OUTPUT_DIR",
+    help="a previous run's nightly/output directory to compute per-benchmark deltas against"
+  )
+  parser.add_argument(
+    "--regression-threshold",
+    type=float,
+    default=10.0,
+    metavar="PERCENT",
+    help="flag benchmarks whose wall time grew by more than PERCENT over --baseline, "
+      "or whose timings grew by more than PERCENT over --history-db (default: 10)"
+  )
+  order_group = parser.add_mutually_exclusive_group()
+  order_group.add_argument(
+    "--order",
+    type=parse_order,
+    default=("name", None),
+    metavar="ORDER",
+    help="name (default), slowest-first, failures-first, or random(SEED); "
+      "slowest-first and failures-first use the --previous run"
+  )
+  order_group.add_argument(
+    "--shuffle",
+    nargs="?",
+    type=int,
+    const=True,
+    metavar="SEED",
+    help="run benchmarks in a random order seeded by SEED (default: a fresh seed, "
+      "recorded in data.json); equivalent to --order random(SEED)"
+  )
+  parser.add_argument(
+    "--previous",
+    type=Path,
+    metavar="DATA_JSON",
+    help="data.json from a previous run, used by --order"
+  )
+  parser.add_argument(
+    "--filter",
+    action="append",
+    default=[],
+    metavar="GLOB",
+    help="only run benchmarks whose path (relative to benchmark_dir) matches GLOB; repeatable"
+  )
+  parser.add_argument(
+    "--skip-pattern",
+    action="append",
+    default=[],
+    metavar="REGEX",
+    help="skip benchmarks whose path (relative to benchmark_dir) matches REGEX; repeatable"
+  )
+  parser.add_argument(
+    "--exclude",
+    action="append",
+    default=[],
+    metavar="PATTERN",
+    help="skip benchmarks whose path (relative to benchmark_dir) contains PATTERN "
+      "or matches it as a glob; repeatable"
+  )
+
+  if config_args.config is not None:
+    config = tomllib.loads(config_args.config.read_text(encoding="utf-8"))
+    # TOML keys may use either the flag spelling or the Python one
+    config = {key.replace("-", "_"): value for (key, value) in config.items()}
+    options = {action.dest for action in parser._actions}
+    unknown = sorted(set(config) - options)
+    if unknown:
+      parser.error(f"unknown option(s) in {config_args.config}: {', '.join(unknown)}")
+    parser.set_defaults(**config)
+
+  args = parser.parse_args()
+  if args.benchmark_dir is None and not args.merge_shards:
+    parser.error("benchmark_dir is required")
+  return args
+
+def parse_shard(value):
+  match = re.fullmatch(r"(\d+)/(\d+)", value)
+  if match is None or not 1 <= int(match.group(1)) <= int(match.group(2)):
+    raise argparse.ArgumentTypeError(f"invalid shard {value!r}: expected K/N with 1 <= K <= N")
+  return (int(match.group(1)), int(match.group(2)))
+
+def merge_shards(shard_dirs):
+  # Combine the output dirs of a --shard K/N run into one output tree, as
+  # if all benchmarks had run on one machine
+  shards = [load_data(shard_dir) for shard_dir in shard_dirs]
+  data = {
+    "generated_at": datetime.now(timezone.utc).isoformat(),
+    # the shards are meant to run on identical machines, so the first one's
+    # host stands for all of them
+    "host": shards[0]["host"],
+    "interrupted": any(shard["interrupted"] for shard in shards),
+    "shard": None,
+    "order": {
+      **shards[0]["order"],
+      "benchmarks": [b for shard in shards for b in shard["order"]["benchmarks"]]
+    },
+    "stopped_on_failure": any(shard["stopped_on_failure"] for shard in shards),
+    # every shard discovers (skips, and dedups) the same benchmarks before
+    # splitting
+    "skipped_benchmarks": shards[0]["skipped_benchmarks"],
+    "aliases": shards[0]["aliases"]
+  }
+  for key in [
+    "failing_benchmarks",
+    "timed_out_benchmarks",
+    "failure_details",
+    "regressed_benchmarks",
+    "known_failures",
+    "unexpected_passes",
+    "passing_benchmarks"
+  ]:
+    data[key] = [item for shard in shards for item in shard[key]]
+  data_out_path = OUTPUT_DIR / "data" / "data.json"
+  data_out_path.parent.mkdir(parents=True, exist_ok=True)
+  data_out_path.write_text(json.dumps(data, indent=2), encoding="utf-8")
+
+  # Interleave the shards' event streams into one timeline
+  events = []
+  for (shard_dir, shard) in zip(shard_dirs, shards):
+    events_path = shard_dir / "events.jsonl"
+    for line in events_path.read_text(encoding="utf-8").splitlines():
+      events.append({**json.loads(line), "shard": shard["shard"]})
+  events.sort(key=lambda event: event["time"])
+  (OUTPUT_DIR / "events.jsonl").write_text(
+    "".join(json.dumps(event) + "\n" for event in events),
+    encoding="utf-8"
+  )
+
+  manifest = {
+    "layout_version": LAYOUT_VERSION,
+    "generated_at": data["generated_at"],
+    "run_modes": ["serve"],
+    "paths": {
+      "data": str(data_out_path.relative_to(OUTPUT_DIR)),
+      "events": "events.jsonl"
+    }
+  }
+  shard_paths = [load_manifest_paths(shard_dir) for shard_dir in shard_dirs]
+
+  # Each shard ran different benchmarks, so their reports don't overlap
+  for (shard_dir, paths) in zip(shard_dirs, shard_paths):
+    if "reports" in paths:
+      shutil.copytree(shard_dir / paths["reports"], REPORT_DIR, dirs_exist_ok=True)
+  if any("reports" in paths for paths in shard_paths):
+    manifest["paths"]["reports"] = str(REPORT_DIR.relative_to(OUTPUT_DIR))
+
+  timeline_csvs = [
+    shard_dir / paths["timeline_csv"]
+    for (shard_dir, paths) in zip(shard_dirs, shard_paths)
+    if "timeline_csv" in paths
+  ]
+  if timeline_csvs:
+    with open(OUTPUT_DIR / "timeline.csv", "w", newline="", encoding="utf-8") as timeline_file:
+      writer = None
+      for timeline_csv in timeline_csvs:
+        with open(timeline_csv, newline="", encoding="utf-8") as shard_file:
+          reader = csv.DictReader(shard_file)
+          if writer is None:
+            writer = csv.DictWriter(timeline_file, reader.fieldnames)
+            writer.writeheader()
+          writer.writerows(reader)
+    manifest["paths"]["timeline_csv"] = "timeline.csv"
+
+  stability_reports = [
+    shard_dir / paths["stability"]
+    for (shard_dir, paths) in zip(shard_dirs, shard_paths)
+    if "stability" in paths
+  ]
+  if stability_reports:
+    stability = [
+      entry
+      for stability_report_path in stability_reports
+      for entry in json.loads(stability_report_path.read_text(encoding="utf-8"))
     ]
-    
-    result = run_command(command)
-    result["benchmark_name"] = benchmark_name
-    result["suite_name"] = suite_name
-    if result["status"] == "success":
-      print(f"Success: {benchmark_name}")
-      results.append(result)
-    else:
-      failing_benchmarks.append(relative_path)
+    stability_out_path = OUTPUT_DIR / "data" / "stability.json"
+    stability_out_path.write_text(json.dumps(stability, indent=2), encoding="utf-8")
+    manifest["paths"]["stability"] = str(stability_out_path.relative_to(OUTPUT_DIR))
 
-  return (results, failing_benchmarks)
+  (OUTPUT_DIR / "manifest.json").write_text(
+    json.dumps(manifest, indent=2),
+    encoding="utf-8"
+  )
+  print(f"Merged {len(shards)} shard(s) into {OUTPUT_DIR}")
+
+def handle_sigterm(signum, frame):
+  # Treat SIGTERM like Ctrl-C so partial results are still written
+  raise KeyboardInterrupt
 
 if __name__ == "__main__":
-  if len(sys.argv) != 2:
-    raise SystemExit(f"Usage: nightly.py <benchmark-dir>")
-  
-  main(sys.argv[1])
+  signal.signal(signal.SIGTERM, handle_sigterm)
+  main(parse_args())
@@ -11,14 +11,33 @@ echo "Beginning POACH nightly script..."
 
 export PATH=~/.cargo/bin:$PATH
 
-# Ensure we start from a clean slate
-rm -rf nightly
+# Ensure we start from a clean slate, unless resuming an interrupted run.
+# --resume may also be set as `resume = true` in a --config file, so the
+# arguments are parsed the way nightly.py parses them.
+RESUME=$(python3 - "$@" <<'EOF'
+import argparse
+import tomllib
+
+parser = argparse.ArgumentParser(add_help=False)
+parser.add_argument("--config")
+parser.add_argument("--resume", action="store_true")
+(args, _) = parser.parse_known_args()
+if args.config is not None and not args.resume:
+  with open(args.config, "rb") as config:
+    args.resume = tomllib.load(config).get("resume", False)
+print(int(bool(args.resume)))
+EOF
+)
+if [ "$RESUME" != 1 ]; then
+  rm -rf nightly
+fi
 mkdir -p nightly
 
 # Standalone runs do their own setup (toolchain + benchmarks clone). When
 # driven by the combined orchestrator, POACH_NIGHTLY_COMBINED=1 and the
 # benchmarks dir is supplied via POACH_BENCHMARKS_DIR.
-if [ -z "${POACH_NIGHTLY_COMBINED:-}" ]; then
+# A resumed run reuses the benchmarks cloned by the interrupted one.
+if [ -z "${POACH_NIGHTLY_COMBINED:-}" ] && ! { [ "$RESUME" = 1 ] && [ -d nightly/tmp/poach-benchmarks ]; }; then
   bash infra/setup.sh
 fi
 
@@ -32,7 +51,11 @@ fi
 cargo build --release
 
 # This script runs all of the benchmarks/experiments
-python3 infra/nightly.py "$BENCHMARKS_DIR"
+# Extra arguments (e.g. --skip-pattern) are forwarded to nightly.py
+# A nonzero status (e.g. too many failures) is reported only after the
+# frontend has been assembled, so the results can still be inspected.
+NIGHTLY_STATUS=0
+python3 infra/nightly.py "$BENCHMARKS_DIR" "$@" || NIGHTLY_STATUS=$?
 
 # Abort if nightly.py failed to produce data.json. Without this check,
 # the nightly runner will report the nightly as successful even though the
@@ -44,5 +67,10 @@ fi
 
 cp infra/nightly-resources/web/* nightly/output
 
+if [ "$NIGHTLY_STATUS" != 0 ]; then
+  echo "ERROR: nightly.py exited with status $NIGHTLY_STATUS."
+  exit "$NIGHTLY_STATUS"
+fi
+
 # Uncomment for local development
 # cd nightly/output && python3 -m http.server 8002
@@ -40,6 +40,9 @@ struct Args {
     /// Number of times to inline leaves
     #[clap(long, default_value = "0")]
     serialize_n_inline_leaves: usize,
+    /// Only serialize these functions (comma-separated; a trailing `*` matches any suffix)
+    #[clap(long, value_delimiter = ',')]
+    serialize_only: Option<Vec<String>>,
     #[clap(short = 'j', long, default_value = "1")]
     /// Number of threads to use for parallel execution. Passing `0` will use the maximum
     /// inferred parallelism available on the current system.
@@ -119,6 +122,7 @@ pub fn cli(mut egraph: EGraph) {
                 let serialized_output = egraph.serialize(SerializeConfig {
                     max_functions: Some(args.max_functions),
                     max_calls_per_function: Some(args.max_calls_per_function),
+                    only_functions: args.serialize_only.clone(),
                     ..SerializeConfig::default()
                 });
                 if !serialized_output.is_complete() {
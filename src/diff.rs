@@ -0,0 +1,92 @@
+use serde_json::Value;
+
+/// Compares two JSON values, returning one line per difference, each annotated
+/// with the path (e.g. `$["nodes"]["function-0-Add"]["children"][1]`) where it occurs.
+pub fn json_diff(a: &Value, b: &Value) -> Vec<String> {
+    let mut differences = Vec::new();
+    diff_at("$".to_string(), a, b, &mut differences);
+    differences
+}
+
+fn diff_at(path: String, a: &Value, b: &Value, differences: &mut Vec<String>) {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, a_value) in a {
+                let key_path = format!("{path}[{key:?}]");
+                match b.get(key) {
+                    Some(b_value) => diff_at(key_path, a_value, b_value, differences),
+                    None => differences.push(format!("{key_path}: only in first ({a_value})")),
+                }
+            }
+            for (key, b_value) in b {
+                if !a.contains_key(key) {
+                    differences.push(format!("{path}[{key:?}]: only in second ({b_value})"));
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for (i, (a_value, b_value)) in a.iter().zip(b).enumerate() {
+                diff_at(format!("{path}[{i}]"), a_value, b_value, differences);
+            }
+            for (i, a_value) in a.iter().enumerate().skip(b.len()) {
+                differences.push(format!("{path}[{i}]: only in first ({a_value})"));
+            }
+            for (i, b_value) in b.iter().enumerate().skip(a.len()) {
+                differences.push(format!("{path}[{i}]: only in second ({b_value})"));
+            }
+        }
+        _ => {
+            if a != b {
+                differences.push(format!("{path}: {a} != {b}"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn equal_values_have_no_differences() {
+        let value = json!({"nodes": {"a": [1, 2, {"op": "Add"}]}, "root": null});
+        assert!(json_diff(&value, &value.clone()).is_empty());
+    }
+
+    #[test]
+    fn missing_keys_are_reported_on_either_side() {
+        let first = json!({"shared": 1, "a": true});
+        let second = json!({"shared": 1, "b": "x"});
+        assert_eq!(
+            json_diff(&first, &second),
+            vec![
+                r#"$["a"]: only in first (true)"#,
+                r#"$["b"]: only in second ("x")"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn array_length_mismatches_report_the_extra_elements() {
+        assert_eq!(
+            json_diff(&json!([1, 2, 3]), &json!([1, 2])),
+            vec!["$[2]: only in first (3)"]
+        );
+        assert_eq!(
+            json_diff(&json!([1]), &json!([1, 2, 3])),
+            vec!["$[1]: only in second (2)", "$[2]: only in second (3)"]
+        );
+    }
+
+    #[test]
+    fn nested_differences_include_the_full_path() {
+        let first = json!({"nodes": {"function-0-Add": {"children": ["x", "y"]}}});
+        let second = json!({"nodes": {"function-0-Add": {"children": ["x", "z"]}}});
+        assert_eq!(
+            json_diff(&first, &second),
+            vec![r#"$["nodes"]["function-0-Add"]["children"][1]: "y" != "z""#]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use serde_json::Value;
+
+/// Render a nightly output directory (its `data.json`) as a self-contained
+/// HTML page, so results can be reviewed without the nightly frontend.
+pub fn html_report(output_dir: &Path) -> std::io::Result<String> {
+    let data = load_data(output_dir)?;
+    let passing = data["passing_benchmarks"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let failures = data["failure_details"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>POACH results</title>\n");
+    html.push_str("<style>table { border-collapse: collapse; } ");
+    html.push_str("td, th { border: 1px solid #ccc; padding: 2px 6px; } ");
+    html.push_str("td.number { text-align: right; }</style>\n");
+    html.push_str("</head>\n<body>\n<h1>POACH results</h1>\n");
+    writeln!(
+        html,
+        "<p>Generated at {}: {} passing, {} failing</p>",
+        escape(&text(&data["generated_at"])),
+        passing.len(),
+        failures.len()
+    )
+    .unwrap();
+
+    html.push_str("<h2>Benchmarks</h2>\n<table>\n<tr>");
+    for heading in [
+        "Suite",
+        "Benchmark",
+        "Wall time (µs)",
+        "Rules (µs)",
+        "Extraction (µs)",
+        "Other (µs)",
+    ] {
+        write!(html, "<th>{heading}</th>").unwrap();
+    }
+    html.push_str("</tr>\n");
+    for benchmark in &passing {
+        let report = &benchmark["report"];
+        write!(
+            html,
+            "<tr><td>{}</td><td>{}</td>",
+            escape(&text(&benchmark["suite_name"])),
+            escape(&text(&benchmark["benchmark_name"]))
+        )
+        .unwrap();
+        for value in [
+            &benchmark["wall_time_micros"],
+            &report["rule_micros"],
+            &report["extraction_micros"],
+            &report["other_micros"],
+        ] {
+            write!(html, "<td class=\"number\">{}</td>", text(value)).unwrap();
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Phases</h2>\n<table>\n<tr>");
+    for heading in ["Benchmark", "Phase", "Start (µs)", "Duration (µs)"] {
+        write!(html, "<th>{heading}</th>").unwrap();
+    }
+    html.push_str("</tr>\n");
+    for benchmark in &passing {
+        // cached or resumed results may predate timelines
+        for phase in benchmark["timeline"].as_array().into_iter().flatten() {
+            writeln!(
+                html,
+                "<tr><td>{}/{}</td><td>{}</td><td class=\"number\">{}</td><td class=\"number\">{}</td></tr>",
+                escape(&text(&benchmark["suite_name"])),
+                escape(&text(&benchmark["benchmark_name"])),
+                escape(&text(&phase["phase"])),
+                text(&phase["start_micros"]),
+                text(&phase["duration_micros"])
+            )
+            .unwrap();
+        }
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Failures</h2>\n<table>\n");
+    html.push_str("<tr><th>Benchmark</th><th>Kind</th><th>Error</th></tr>\n");
+    for failure in &failures {
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td><pre>{}</pre></td></tr>",
+            escape(&text(&failure["benchmark"])),
+            escape(&text(&failure["failure_kind"])),
+            escape(&text(&failure["error"]))
+        )
+        .unwrap();
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    Ok(html)
+}
+
+/// Older output directories predate `manifest.json`, so fall back to the
+/// conventional `data/data.json` path.
+fn load_data(output_dir: &Path) -> std::io::Result<Value> {
+    let manifest_path = output_dir.join("manifest.json");
+    let data_path = if manifest_path.exists() {
+        let manifest: Value = serde_json::from_str(&std::fs::read_to_string(manifest_path)?)?;
+        output_dir.join(text(&manifest["paths"]["data"]))
+    } else {
+        output_dir.join("data").join("data.json")
+    };
+    Ok(serde_json::from_str(&std::fs::read_to_string(data_path)?)?)
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn output_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "poach_html_report_{name}_{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(dir.join("data")).unwrap();
+        dir
+    }
+
+    fn data() -> Value {
+        json!({
+            "generated_at": "2026-01-01T00:00:00+00:00",
+            "passing_benchmarks": [{
+                "suite_name": "math/train",
+                "benchmark_name": "a<b>.egg",
+                "wall_time_micros": 1234,
+                "report": {"rule_micros": 1000, "extraction_micros": 200, "other_micros": 34},
+                "timeline": [{"phase": "running_rules", "start_micros": 0, "duration_micros": 1000}]
+            }],
+            "failure_details": [{
+                "benchmark": "math/train/b.egg",
+                "failure_kind": "run_error",
+                "error": "expected \"&\""
+            }]
+        })
+    }
+
+    #[test]
+    fn renders_rows_and_escapes_text() {
+        let dir = output_dir("rows");
+        std::fs::write(dir.join("data").join("data.json"), data().to_string()).unwrap();
+        let html = html_report(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(html.contains("1 passing, 1 failing"));
+        assert!(html.contains("<tr><td>math/train</td><td>a&lt;b&gt;.egg</td>"));
+        assert!(html.contains("<td class=\"number\">1234</td>"));
+        assert!(html.contains("<td>running_rules</td>"));
+        assert!(html.contains("<pre>expected &quot;&amp;&quot;</pre>"));
+        assert!(!html.contains("a<b>"));
+    }
+
+    #[test]
+    fn reads_data_from_the_manifest_path() {
+        let dir = output_dir("manifest");
+        std::fs::write(dir.join("results.json"), data().to_string()).unwrap();
+        std::fs::write(
+            dir.join("manifest.json"),
+            json!({"paths": {"data": "results.json"}}).to_string(),
+        )
+        .unwrap();
+        let html = html_report(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(html.contains("a&lt;b&gt;.egg"));
+    }
+}
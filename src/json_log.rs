@@ -0,0 +1,171 @@
+use std::fs::File;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::{Map, Value, json};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+/// A tracing layer that writes one JSON object per line to a file: one for
+/// each event, with the spans it occurred in, and one when each span closes,
+/// with how long it was open. Lines carry a timestamp so poach's own
+/// overhead can be lined up with the phases of a benchmark.
+pub struct JsonLayer {
+    writer: Mutex<File>,
+}
+
+impl JsonLayer {
+    pub fn new(file: File) -> Self {
+        Self {
+            writer: Mutex::new(file),
+        }
+    }
+
+    fn write(&self, mut line: Value) {
+        line["timestamp_micros"] = json!(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_micros() as u64
+        );
+        // Logging must not make a run fail, so write errors are dropped
+        let _ = writeln!(self.writer.lock().unwrap(), "{line}");
+    }
+}
+
+/// Stored in each span's extensions when it is created.
+struct SpanData {
+    fields: Map<String, Value>,
+    opened: Instant,
+}
+
+impl<S> Layer<S> for JsonLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Map::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanData {
+                fields,
+                opened: Instant::now(),
+            });
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Map::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        let spans: Vec<Value> = ctx
+            .event_scope(event)
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+            .map(|span| {
+                let fields = span
+                    .extensions()
+                    .get::<SpanData>()
+                    .map(|data| data.fields.clone())
+                    .unwrap_or_default();
+                json!({"name": span.name(), "fields": fields})
+            })
+            .collect();
+        self.write(json!({
+            "level": event.metadata().level().as_str(),
+            "target": event.metadata().target(),
+            "spans": spans,
+            "fields": fields,
+        }));
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let extensions = span.extensions();
+        let Some(data) = extensions.get::<SpanData>() else {
+            return;
+        };
+        self.write(json!({
+            "span_closed": span.name(),
+            "fields": data.fields,
+            "duration_micros": data.opened.elapsed().as_micros() as u64,
+        }));
+    }
+}
+
+struct FieldVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), json!(format!("{value:?}")));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::{info, info_span};
+    use tracing_subscriber::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn writes_events_with_their_spans_and_span_durations() {
+        let path = std::env::temp_dir().join(format!(
+            "poach_json_log_{}.jsonl",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let subscriber =
+            tracing_subscriber::registry().with(JsonLayer::new(File::create(&path).unwrap()));
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = info_span!("program", path = "a.egg").entered();
+            info!(ran = true, "finished");
+        });
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["fields"]["message"], "finished");
+        assert_eq!(lines[0]["fields"]["ran"], true);
+        assert_eq!(
+            lines[0]["spans"],
+            json!([{"name": "program", "fields": {"path": "a.egg"}}])
+        );
+        assert_eq!(lines[1]["span_closed"], "program");
+        assert!(lines[1]["duration_micros"].is_u64());
+    }
+}
@@ -1,4 +1,8 @@
+mod diff;
+mod html_report;
+mod json_log;
 mod poach;
+mod stats;
 use poach::poach;
 
 #[cfg(feature = "bin")]
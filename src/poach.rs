@@ -1,11 +1,29 @@
 use std::path::PathBuf;
 
-use clap::{Args, Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand};
+use tracing::info_span;
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+
+use crate::diff::json_diff;
+use crate::html_report::html_report;
+use crate::json_log::JsonLayer;
+use crate::stats::corpus_stats;
 
 #[derive(Debug, Parser)]
 #[command(version, about)]
 #[command(propagate_version = true)]
 struct Cli {
+    /// Log progress to stderr: -v for each phase, -vv for more detail
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Also write logs, with span timings, as JSON lines to this file; these
+    /// include each phase even without -v
+    #[arg(long, global = true)]
+    log_json: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -18,6 +36,12 @@ enum Commands {
     Serve(ServeArgs),
     /// Update a model with new input-output pairs
     FineTune(FineTuneArgs),
+    /// Report statistics about a benchmark corpus
+    Stats(StatsArgs),
+    /// Compare two serialized egraphs, exiting nonzero if they differ
+    Diff(DiffArgs),
+    /// Render a nightly output directory as a standalone HTML page
+    Report(ReportArgs),
     /// TEST
     Test(TestArgs),
 }
@@ -95,11 +119,52 @@ struct FineTuneArgs {
     output_model_file: PathBuf,
 }
 
+#[derive(Debug, Args)]
+struct StatsArgs {
+    /// Requires a directory
+    corpus_dir: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct DiffArgs {
+    /// Requires two JSON files
+    first: PathBuf,
+    second: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct ReportArgs {
+    /// Requires a nightly output directory; report.html is written there
+    output_dir: PathBuf,
+}
+
 #[derive(Debug, Args)]
 struct TestArgs {}
 
 pub fn poach() {
     let cli = Cli::parse();
+    let level = match cli.verbose {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        _ => LevelFilter::DEBUG,
+    };
+    // serve reports its statistics as JSON on stderr, so it only gets
+    // stderr logs when asked for them
+    let stderr_layer = (cli.verbose > 0 || !matches!(cli.command, Commands::Serve(_))).then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_filter(level)
+    });
+    // The JSON log exists for its phase spans, which are logged at INFO
+    let json_layer = cli.log_json.as_ref().map(|path| {
+        let file = std::fs::File::create(path)
+            .unwrap_or_else(|err| panic!("Failed to create {path:?}: {err}"));
+        JsonLayer::new(file).with_filter(level.max(LevelFilter::INFO))
+    });
+    tracing_subscriber::registry()
+        .with(stderr_layer)
+        .with(json_layer)
+        .init();
     match cli.command {
         Commands::Train(arg) => {
             train(arg);
@@ -110,6 +175,15 @@ pub fn poach() {
         Commands::FineTune(arg) => {
             fine_tune(arg);
         }
+        Commands::Stats(arg) => {
+            stats(arg);
+        }
+        Commands::Diff(arg) => {
+            diff(arg);
+        }
+        Commands::Report(arg) => {
+            report(arg);
+        }
         Commands::Test(arg) => {
             println!("test({:?})", arg);
         }
@@ -123,6 +197,7 @@ fn train(arg: TrainArgs) {
 }
 
 fn serve(arg: ServeArgs) {
+    let _span = info_span!("serve", model = %arg.model_file.display()).entered();
     println!("serve({:?})", arg);
     //TODO
 }
@@ -131,3 +206,34 @@ fn fine_tune(arg: FineTuneArgs) {
     println!("fine_tune({:?})", arg);
     //TODO
 }
+
+fn stats(arg: StatsArgs) {
+    let stats = corpus_stats(&arg.corpus_dir)
+        .unwrap_or_else(|err| panic!("Failed to read corpus {:?}: {err}", arg.corpus_dir));
+    println!("{}", serde_json::to_string_pretty(&stats).unwrap());
+}
+
+fn diff(arg: DiffArgs) {
+    let read = |path: &PathBuf| -> serde_json::Value {
+        let contents = std::fs::read_to_string(path)
+            .unwrap_or_else(|err| panic!("Failed to read {path:?}: {err}"));
+        serde_json::from_str(&contents)
+            .unwrap_or_else(|err| panic!("Failed to parse {path:?}: {err}"))
+    };
+    let differences = json_diff(&read(&arg.first), &read(&arg.second));
+    for difference in &differences {
+        println!("{difference}");
+    }
+    if !differences.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+fn report(arg: ReportArgs) {
+    let html = html_report(&arg.output_dir)
+        .unwrap_or_else(|err| panic!("Failed to read results in {:?}: {err}", arg.output_dir));
+    let report_path = arg.output_dir.join("report.html");
+    std::fs::write(&report_path, html)
+        .unwrap_or_else(|err| panic!("Failed to write {report_path:?}: {err}"));
+    println!("Wrote {}", report_path.display());
+}
@@ -12,6 +12,8 @@ pub struct SerializeConfig {
     pub include_temporary_functions: bool,
     // Root eclasses to include in the output
     pub root_eclasses: Vec<(ArcSort, Value)>,
+    // Names of the functions to include, any others are left out; a trailing `*` matches any suffix
+    pub only_functions: Option<Vec<String>>,
 }
 
 /// Output of serializing an e-graph, including values that were omitted if any.
@@ -48,6 +50,15 @@ impl SerializeOutput {
     }
 }
 
+/// Whether `name` matches `pattern`, which is either an exact function name
+/// or a prefix followed by `*`.
+fn matches_function(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => pattern == name,
+    }
+}
+
 #[allow(dead_code)]
 struct Serializer {
     node_ids: NodeIDs,
@@ -63,6 +74,7 @@ impl Default for SerializeConfig {
             max_calls_per_function: None,
             include_temporary_functions: false,
             root_eclasses: vec![],
+            only_functions: None,
         }
     }
 }
@@ -138,6 +150,14 @@ impl EGraph {
         let mut functions_kept = 0usize;
         let mut let_bindings = HashMap::default();
         for (name, function) in self.functions.iter() {
+            if let Some(patterns) = &config.only_functions {
+                if !patterns
+                    .iter()
+                    .any(|pattern| matches_function(pattern, name))
+                {
+                    continue;
+                }
+            }
             if functions_kept >= max_functions {
                 discarded_functions.push(name.clone());
                 continue;
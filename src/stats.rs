@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use ::poach::ast::{Action, Change, Command, Parser};
+use ::poach::{EGraph, file_supports_proofs};
+use serde::Serialize;
+use tracing::{debug, info, info_span};
+
+/// Summary of a benchmark corpus, used to judge how representative it is.
+#[derive(Debug, Default, Serialize)]
+pub struct CorpusStats {
+    pub num_programs: usize,
+    /// Programs that failed to parse or run; they have no egraph size
+    pub num_failed: usize,
+    /// Number of programs per suite (the directory containing the program)
+    pub suites: BTreeMap<String, usize>,
+    pub total_bytes: usize,
+    pub average_bytes: f64,
+    pub uses_containers: usize,
+    pub supports_proofs: usize,
+    pub uses_subsumption: usize,
+    pub total_tuples: usize,
+    pub average_tuples: f64,
+    pub max_tuples: usize,
+    pub programs: Vec<ProgramStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProgramStats {
+    pub path: PathBuf,
+    pub suite: String,
+    pub bytes: usize,
+    pub uses_containers: bool,
+    pub supports_proofs: bool,
+    pub uses_subsumption: bool,
+    /// Size of the egraph after running the program, if it ran successfully
+    pub num_tuples: Option<usize>,
+}
+
+/// Walk `corpus_dir` for `.egg` programs, run each one, and collect statistics.
+pub fn corpus_stats(corpus_dir: &Path) -> std::io::Result<CorpusStats> {
+    let mut paths = Vec::new();
+    info_span!("discovery").in_scope(|| find_programs(corpus_dir, &mut paths))?;
+    paths.sort();
+    info!(num_programs = paths.len(), "discovered programs");
+
+    let mut stats = CorpusStats::default();
+    for path in paths {
+        let program = program_stats(corpus_dir, path)?;
+        *stats.suites.entry(program.suite.clone()).or_default() += 1;
+        stats.total_bytes += program.bytes;
+        stats.uses_containers += program.uses_containers as usize;
+        stats.supports_proofs += program.supports_proofs as usize;
+        stats.uses_subsumption += program.uses_subsumption as usize;
+        match program.num_tuples {
+            Some(num_tuples) => {
+                stats.total_tuples += num_tuples;
+                stats.max_tuples = stats.max_tuples.max(num_tuples);
+            }
+            None => stats.num_failed += 1,
+        }
+        stats.programs.push(program);
+    }
+
+    stats.num_programs = stats.programs.len();
+    if stats.num_programs > 0 {
+        stats.average_bytes = stats.total_bytes as f64 / stats.num_programs as f64;
+    }
+    let num_succeeded = stats.num_programs - stats.num_failed;
+    if num_succeeded > 0 {
+        stats.average_tuples = stats.total_tuples as f64 / num_succeeded as f64;
+    }
+    Ok(stats)
+}
+
+fn find_programs(dir: &Path, paths: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_programs(&path, paths)?;
+        } else if path.extension().is_some_and(|ext| ext == "egg") {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn program_stats(corpus_dir: &Path, path: PathBuf) -> std::io::Result<ProgramStats> {
+    let _span = info_span!("program", path = %path.display()).entered();
+    let source = std::fs::read_to_string(&path)?;
+    let suite = path
+        .parent()
+        .and_then(|parent| parent.strip_prefix(corpus_dir).ok())
+        .map(|suite| suite.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut egraph = EGraph::default();
+    let ran = info_span!("run").in_scope(|| {
+        egraph
+            .parse_and_run_program(path.to_str().map(String::from), &source)
+            .inspect_err(|err| debug!(%err, "program failed"))
+            .is_ok()
+    });
+    info!(ran, "finished");
+
+    Ok(ProgramStats {
+        suite,
+        bytes: source.len(),
+        uses_containers: !egraph
+            .get_arcsorts_by(|sort| sort.is_container_sort())
+            .is_empty(),
+        supports_proofs: file_supports_proofs(&path),
+        uses_subsumption: Parser::default()
+            .get_program_from_string(None, &source)
+            .is_ok_and(|commands| uses_subsumption(&commands)),
+        num_tuples: ran.then(|| egraph.num_tuples()),
+        path,
+    })
+}
+
+/// Whether any command subsumes, either with a `subsume` action (at the top
+/// level or in a rule) or with a `rewrite` marked `:subsume`.
+fn uses_subsumption(commands: &[Command]) -> bool {
+    let subsumes = |action: &Action| matches!(action, Action::Change(_, Change::Subsume, _, _));
+    commands.iter().any(|command| match command {
+        Command::Rewrite(_, _, subsume) => *subsume,
+        Command::Rule { rule } => rule.head.0.iter().any(subsumes),
+        Command::Action(action) => subsumes(action),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corpus_stats_counts_suites_failures_containers_and_subsumption() {
+        let corpus_dir = std::env::temp_dir().join(format!(
+            "poach_corpus_stats_{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(corpus_dir.join("containers")).unwrap();
+        std::fs::create_dir_all(corpus_dir.join("broken")).unwrap();
+        std::fs::create_dir_all(corpus_dir.join("subsumption")).unwrap();
+        std::fs::write(
+            corpus_dir.join("containers").join("vec.egg"),
+            "; a comment mentioning (subsume doesn't count
+             (sort IntVec (Vec i64))
+             (function len-of (IntVec) i64 :no-merge)
+             (set (len-of (vec-of 1 2)) 2)
+             (set (len-of (vec-of 3)) 1)",
+        )
+        .unwrap();
+        std::fs::write(
+            corpus_dir.join("subsumption").join("rewrite.egg"),
+            "(datatype Math (Num i64) (Mul Math Math))
+             (rewrite (Mul a b) (Mul b a) :subsume)",
+        )
+        .unwrap();
+        std::fs::write(corpus_dir.join("broken").join("unclosed.egg"), "(sort").unwrap();
+
+        let stats = corpus_stats(&corpus_dir).unwrap();
+        std::fs::remove_dir_all(&corpus_dir).unwrap();
+
+        assert_eq!(stats.num_programs, 3);
+        assert_eq!(
+            stats.suites,
+            BTreeMap::from([
+                ("broken".to_string(), 1),
+                ("containers".to_string(), 1),
+                ("subsumption".to_string(), 1)
+            ])
+        );
+        assert_eq!(stats.num_failed, 1);
+        assert_eq!(stats.uses_containers, 1);
+        assert_eq!(stats.uses_subsumption, 1);
+        // 2 tuples in vec.egg and none in rewrite.egg
+        assert_eq!(stats.average_tuples, 1.0);
+    }
+}
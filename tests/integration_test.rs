@@ -944,6 +944,7 @@ fn test_serialize_message_max_functions() {
         max_calls_per_function: None,
         include_temporary_functions: false,
         root_eclasses: vec![],
+        only_functions: None,
     });
     assert!(!serialize_output.is_complete());
     assert_eq!(serialize_output.omitted_description(), "Omitted: c\n");
@@ -968,7 +969,38 @@ fn test_serialize_message_max_calls_per_function() {
         max_calls_per_function: Some(2),
         include_temporary_functions: false,
         root_eclasses: vec![],
+        only_functions: None,
     });
     assert!(!serialize_output.is_complete());
     assert_eq!(serialize_output.omitted_description(), "Truncated: mk\n");
 }
+
+#[test]
+fn test_serialize_only_functions() {
+    let mut egraph = EGraph::default();
+    egraph
+        .parse_and_run_program(
+            None,
+            r#"
+            (datatype Math (Num i64) (Neg Math))
+            (datatype Other (Leaf))
+            (Neg (Num 1))
+            (Leaf)
+            "#,
+        )
+        .unwrap();
+    let serialize_output = egraph.serialize(SerializeConfig {
+        only_functions: Some(vec!["Neg".to_string(), "Nu*".to_string()]),
+        ..SerializeConfig::default()
+    });
+    assert!(serialize_output.is_complete());
+    let mut ops: Vec<_> = serialize_output
+        .egraph
+        .nodes
+        .values()
+        .filter(|node| node.op != "1")
+        .map(|node| node.op.as_str())
+        .collect();
+    ops.sort();
+    assert_eq!(ops, vec!["Neg", "Num"]);
+}